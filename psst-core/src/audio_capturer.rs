@@ -1,9 +1,16 @@
-use std::{fs::File, io, path::PathBuf, sync::Arc, thread};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
 
 use crate::{
-    audio_file::{AudioFile, RawFileSource},
+    audio_file::{AudioFile, AudioFileFormat, RawFileSource},
     audio_player::{load_audio_key, load_audio_path, PlaybackConfig},
     cache::CacheHandle,
     cdn::CdnHandle,
@@ -12,11 +19,46 @@ use crate::{
     session::SessionService,
 };
 
+/// Quality selection for a capture, expanded into an ordered list of candidate
+/// formats that are tried in turn until one resolves to a real file id.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum QualityPreset {
+    /// Only the standard Ogg Vorbis bitrate, without falling back.
+    OggOnly,
+    /// The best available Ogg Vorbis bitrate, degrading gracefully.
+    BestBitrate,
+}
+
+impl QualityPreset {
+    /// Candidate formats in the order they should be attempted.
+    fn formats(self) -> &'static [AudioFileFormat] {
+        match self {
+            QualityPreset::OggOnly => &[AudioFileFormat::OGG_VORBIS_160],
+            QualityPreset::BestBitrate => &[
+                AudioFileFormat::OGG_VORBIS_320,
+                AudioFileFormat::OGG_VORBIS_160,
+                AudioFileFormat::OGG_VORBIS_96,
+            ],
+        }
+    }
+}
+
+impl Default for QualityPreset {
+    fn default() -> Self {
+        QualityPreset::BestBitrate
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CaptureItem {
     pub item_id: ItemId,
     pub name: Arc<str>,
     pub artist: Arc<str>,
+    pub album: Arc<str>,
+    pub track_number: u32,
+    pub disc_number: u32,
+    pub duration: Duration,
+    pub cover_url: Option<Arc<str>>,
 }
 
 impl CaptureItem {
@@ -26,26 +68,63 @@ impl CaptureItem {
         cdn: CdnHandle,
         cache: CacheHandle,
         config: &PlaybackConfig,
-    ) -> Result<LoadedCaptureItem, Error> {
-        let path = load_audio_path(self.item_id, session, &cache, config)?;
-        let key = load_audio_key(&path, session, &cache)?;
-        let file = AudioFile::open(path, cdn, cache)?;
-        let source = file.raw_source(key)?;
-        Ok(LoadedCaptureItem { source })
+        quality: QualityPreset,
+    ) -> Result<LoadedCaptureItem, CaptureError> {
+        let mut last_err = None;
+        for &format in quality.formats() {
+            match load_audio_path(self.item_id, session, &cache, config, format) {
+                Ok(path) => {
+                    // Once a format resolves, any further failure is an I/O-ish
+                    // problem (key fetch, CDN open) worth retrying.
+                    let key =
+                        load_audio_key(&path, session, &cache).map_err(CaptureError::transient)?;
+                    let file = AudioFile::open(path, cdn.clone(), cache.clone())
+                        .map_err(CaptureError::transient)?;
+                    let source = file.raw_source(key).map_err(CaptureError::transient)?;
+                    let total_size = source.len();
+                    return Ok(LoadedCaptureItem {
+                        source,
+                        format,
+                        total_size,
+                    });
+                }
+                Err(err) => {
+                    log::debug!("format {:?} unavailable for {}: {}", format, self.name, err);
+                    last_err = Some(err);
+                }
+            }
+        }
+        // No candidate format resolved: the track is unavailable, not worth
+        // requeueing.
+        Err(CaptureError::fatal(
+            last_err.expect("QualityPreset always yields at least one format"),
+        ))
     }
 }
 
 pub struct LoadedCaptureItem {
     source: RawFileSource,
+    format: AudioFileFormat,
+    /// Expected total byte length of the track, used to report progress as a
+    /// true percentage.
+    total_size: u64,
+}
+
+/// A single unit of work handed to a worker thread, carrying a snapshot of the
+/// capture settings as they were when the item was enqueued.
+struct Job {
+    item: CaptureItem,
+    config: PlaybackConfig,
+    quality: QualityPreset,
+    tagging: bool,
 }
 
 pub struct Capturer {
     state: CapturerState,
-    session: SessionService,
-    cdn: CdnHandle,
-    cache: CacheHandle,
     config: PlaybackConfig,
-    destination_dir: PathBuf,
+    quality: QualityPreset,
+    tagging: bool,
+    job_sender: Sender<Job>,
     event_sender: Sender<CapturerEvent>,
     event_receiver: Receiver<CapturerEvent>,
 }
@@ -59,18 +138,45 @@ impl Capturer {
         destination_dir: PathBuf,
     ) -> Self {
         let (event_sender, event_receiver) = unbounded();
+        let (job_sender, job_receiver) = unbounded::<Job>();
+
+        // Load the ledger and sweep any stale `.part` files left by a crash so
+        // those items get retried on the next download.
+        let ledger = Arc::new(Mutex::new(ledger::Ledger::load(&destination_dir)));
+
+        // Spawn a fixed pool of long-lived workers that drain the job queue,
+        // rather than one thread per download hammering the CDN at once.
+        let worker_count = config.max_concurrency.max(1);
+        for _ in 0..worker_count {
+            let jobs = job_receiver.clone();
+            let events = event_sender.clone();
+            let session = session.clone();
+            let cdn = cdn.clone();
+            let cache = cache.clone();
+            let destination_dir = destination_dir.clone();
+            let ledger = Arc::clone(&ledger);
+            thread::spawn(move || {
+                while let Ok(job) = jobs.recv() {
+                    capture(job, &session, &cdn, &cache, &destination_dir, &ledger, &events);
+                }
+            });
+        }
+
         Self {
-            session,
-            cdn,
-            cache,
             config,
-            destination_dir,
+            quality: QualityPreset::default(),
+            tagging: true,
+            job_sender,
             event_sender,
             event_receiver,
-            state: CapturerState::Idle,
+            state: CapturerState::default(),
         }
     }
 
+    pub fn state(&self) -> &CapturerState {
+        &self.state
+    }
+
     pub fn event_sender(&self) -> Sender<CapturerEvent> {
         self.event_sender.clone()
     }
@@ -87,71 +193,638 @@ impl Capturer {
             CapturerEvent::Downloaded { item } => {
                 self.handle_downloaded(item);
             }
-            CapturerEvent::Downloading { .. } => {}
+            CapturerEvent::Failed { .. } => {
+                self.state.in_flight = self.state.in_flight.saturating_sub(1);
+            }
+            CapturerEvent::Downloading { .. } => {
+                self.state.pending = self.state.pending.saturating_sub(1);
+                self.state.in_flight += 1;
+            }
+            CapturerEvent::Progress { .. } => {}
         }
     }
 
     fn handle_command(&mut self, cmd: CapturerCommand) {
         match cmd {
             CapturerCommand::Download { item } => self.download(item),
-            CapturerCommand::Configure { config } => self.configure(config),
+            CapturerCommand::DownloadBatch { items } => self.download_batch(items),
+            CapturerCommand::Configure {
+                config,
+                quality,
+                tagging,
+            } => self.configure(config, quality, tagging),
         }
     }
 
     fn download(&mut self, item: CaptureItem) {
-        self.event_sender
-            .send(CapturerEvent::Downloading { item: item.clone() })
-            .expect("Failed to send CapturerEvent::Downloading");
-        self.state = CapturerState::Downloading { item: item.clone() };
-
-        thread::spawn({
-            let event_sender = self.event_sender.clone();
-            let session = self.session.clone();
-            let cdn = self.cdn.clone();
-            let cache = self.cache.clone();
-            let config = self.config.clone();
-            let destination_dir = self.destination_dir.clone();
-            move || {
-                let load_result = item.load(&session, cdn, cache, &config);
-                match load_result {
-                    Ok(mut loaded_item) => {
-                        let mut file = File::create(
-                            destination_dir.join(format!("{} - {}.ogg", item.artist, item.name)),
-                        )
-                        .unwrap();
-                        io::copy(&mut loaded_item.source, &mut file).unwrap();
-                    }
-                    Err(err) => {
-                        log::error!("skipping, error while loading: {}", err);
-                    }
-                };
-                event_sender
-                    .send(CapturerEvent::Downloaded { item })
-                    .expect("Failed to send CapturerEvent::Downloaded");
-            }
-        });
+        self.enqueue(item);
     }
 
-    fn handle_downloaded(&mut self, item: CaptureItem) {}
+    fn download_batch(&mut self, items: Vec<CaptureItem>) {
+        for item in items {
+            self.enqueue(item);
+        }
+    }
 
-    fn configure(&mut self, config: PlaybackConfig) {
+    /// Snapshot the current settings and hand the item to the worker pool,
+    /// bumping the pending count so callers can render overall progress.
+    fn enqueue(&mut self, item: CaptureItem) {
+        let job = Job {
+            item,
+            config: self.config.clone(),
+            quality: self.quality,
+            tagging: self.tagging,
+        };
+        self.state.pending += 1;
+        self.job_sender
+            .send(job)
+            .expect("Capturer workers have stopped");
+    }
+
+    fn handle_downloaded(&mut self, _item: CaptureItem) {
+        self.state.in_flight = self.state.in_flight.saturating_sub(1);
+    }
+
+    fn configure(&mut self, config: PlaybackConfig, quality: QualityPreset, tagging: bool) {
         self.config = config;
+        self.quality = quality;
+        self.tagging = tagging;
     }
 }
 
 pub enum CapturerCommand {
     Download { item: CaptureItem },
-    Configure { config: PlaybackConfig },
+    DownloadBatch { items: Vec<CaptureItem> },
+    Configure {
+        config: PlaybackConfig,
+        quality: QualityPreset,
+        tagging: bool,
+    },
 }
 
 pub enum CapturerEvent {
     Command(CapturerCommand),
     Downloading { item: CaptureItem },
     Downloaded { item: CaptureItem },
+    Failed { item: CaptureItem, error: CaptureError },
+    Progress {
+        item: CaptureItem,
+        bytes_done: u64,
+        bytes_total: u64,
+    },
 }
 
-enum CapturerState {
-    Idle,
-    Downloading { item: CaptureItem },
-    Invalid,
+/// The outcome of a failed capture, pairing the underlying error with whether
+/// the item is worth requeueing.
+#[derive(Debug)]
+pub struct CaptureError {
+    pub error: Error,
+    pub kind: FailureKind,
+}
+
+impl CaptureError {
+    /// A failure that may succeed on a later attempt, e.g. a dropped CDN
+    /// connection.
+    fn transient(error: Error) -> Self {
+        Self {
+            error,
+            kind: FailureKind::Transient,
+        }
+    }
+
+    /// A failure that will not resolve on retry, e.g. a track that is
+    /// unavailable in every candidate format.
+    fn fatal(error: Error) -> Self {
+        Self {
+            error,
+            kind: FailureKind::Fatal,
+        }
+    }
+
+    /// Turn a local I/O failure into a [`CaptureError`], classifying connection
+    /// hiccups as transient and everything else (full disk, permissions) as
+    /// fatal.
+    fn from_io(error: io::Error) -> Self {
+        use io::ErrorKind::*;
+        let kind = match error.kind() {
+            ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof | TimedOut
+            | Interrupted => FailureKind::Transient,
+            _ => FailureKind::Fatal,
+        };
+        Self {
+            error: error.into(),
+            kind,
+        }
+    }
+}
+
+/// Whether a failed capture is worth retrying.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FailureKind {
+    /// Retryable — requeue the item later.
+    Transient,
+    /// Not retryable as-is — surface to the user.
+    Fatal,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapturerState {
+    /// Items enqueued but not yet picked up by a worker.
+    pub pending: usize,
+    /// Items currently being downloaded by a worker.
+    pub in_flight: usize,
+}
+
+/// Run a single capture job on a worker thread: emit [`CapturerEvent::Downloading`],
+/// load and copy the track (tagging it when enabled), then emit
+/// [`CapturerEvent::Downloaded`], or [`CapturerEvent::Failed`] if the capture
+/// failed.
+fn capture(
+    job: Job,
+    session: &SessionService,
+    cdn: &CdnHandle,
+    cache: &CacheHandle,
+    destination_dir: &Path,
+    ledger: &Mutex<ledger::Ledger>,
+    events: &Sender<CapturerEvent>,
+) {
+    let Job {
+        item,
+        config,
+        quality,
+        tagging,
+    } = job;
+
+    // Always announce the item as in-flight first so the pending/in-flight
+    // accounting balances even when we skip the actual download below.
+    events
+        .send(CapturerEvent::Downloading { item: item.clone() })
+        .expect("Failed to send CapturerEvent::Downloading");
+
+    // Skip items the ledger already marks complete, as long as the file is
+    // still present at the recorded size.
+    if ledger.lock().unwrap().is_complete(item.item_id) {
+        events
+            .send(CapturerEvent::Downloaded { item })
+            .expect("Failed to send CapturerEvent::Downloaded");
+        return;
+    }
+
+    let event = match download_item(
+        &item,
+        session,
+        cdn,
+        cache,
+        destination_dir,
+        ledger,
+        &config,
+        quality,
+        tagging,
+        events,
+    ) {
+        Ok(()) => CapturerEvent::Downloaded { item },
+        Err(error) => {
+            log::error!("capture failed for {}: {}", item.name, error.error);
+            CapturerEvent::Failed { item, error }
+        }
+    };
+    events
+        .send(event)
+        .expect("Failed to send capture completion event");
+}
+
+/// Load, copy and tag a single track, propagating every load, copy and rename
+/// failure as a [`CaptureError`] rather than panicking the worker thread.
+/// Tagging failures are only logged.
+#[allow(clippy::too_many_arguments)]
+fn download_item(
+    item: &CaptureItem,
+    session: &SessionService,
+    cdn: &CdnHandle,
+    cache: &CacheHandle,
+    destination_dir: &Path,
+    ledger: &Mutex<ledger::Ledger>,
+    config: &PlaybackConfig,
+    quality: QualityPreset,
+    tagging: bool,
+    events: &Sender<CapturerEvent>,
+) -> Result<(), CaptureError> {
+    let mut loaded_item = item.load(session, cdn.clone(), cache.clone(), config, quality)?;
+
+    let destination = destination_dir.join(format!("{} - {}.ogg", item.artist, item.name));
+    // Copy into a `.part` sibling so a crash mid-copy never leaves a truncated
+    // `.ogg`; only rename into place once it fully succeeds.
+    let part = ledger::part_path(&destination);
+    let mut file = File::create(&part).map_err(CaptureError::from_io)?;
+    copy_with_progress(
+        item,
+        &mut loaded_item.source,
+        &mut file,
+        loaded_item.total_size,
+        events,
+    )
+    .map_err(CaptureError::from_io)?;
+    drop(file);
+
+    if tagging {
+        let cover = item
+            .cover_url
+            .as_deref()
+            .and_then(|url| cdn.fetch_file(url).ok());
+        if let Err(err) = tags::write_ogg_tags(&part, item, loaded_item.format, cover.as_deref()) {
+            // Tagging is best-effort: keep the untagged file rather than fail.
+            log::warn!("failed to tag {}: {}", item.name, err);
+        }
+    }
+
+    std::fs::rename(&part, &destination).map_err(CaptureError::from_io)?;
+    // Record the length of the file as it lands on disk (tagging rewrites it),
+    // so `is_complete` can match it on a later run.
+    let length = std::fs::metadata(&destination)
+        .map_err(CaptureError::from_io)?
+        .len();
+    ledger
+        .lock()
+        .unwrap()
+        .mark_complete(item.item_id, loaded_item.format, destination, length);
+    Ok(())
+}
+
+/// Stream `source` into `file` in fixed-size chunks, emitting throttled
+/// [`CapturerEvent::Progress`] events (a few per second) against the known
+/// total size. Returns the number of bytes written.
+fn copy_with_progress(
+    item: &CaptureItem,
+    source: &mut RawFileSource,
+    file: &mut File,
+    bytes_total: u64,
+    events: &Sender<CapturerEvent>,
+) -> io::Result<u64> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+    const THROTTLE: Duration = Duration::from_millis(250);
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut bytes_done = 0u64;
+    let mut last_emit: Option<Instant> = None;
+
+    loop {
+        let read = source.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        bytes_done += read as u64;
+
+        let now = Instant::now();
+        if last_emit.is_none_or(|at| now.duration_since(at) >= THROTTLE) {
+            last_emit = Some(now);
+            emit_progress(events, item, bytes_done, bytes_total);
+        }
+    }
+
+    // Always land on a final event so the UI can settle at 100%.
+    emit_progress(events, item, bytes_done, bytes_total);
+    Ok(bytes_done)
+}
+
+fn emit_progress(
+    events: &Sender<CapturerEvent>,
+    item: &CaptureItem,
+    bytes_done: u64,
+    bytes_total: u64,
+) {
+    let _ = events.send(CapturerEvent::Progress {
+        item: item.clone(),
+        bytes_done,
+        bytes_total,
+    });
+}
+
+/// Writing Vorbis comments (and an optional cover-art picture block) into the
+/// Ogg containers produced by `download_item`.
+mod tags {
+    use std::{
+        fs::{self, File},
+        io::BufReader,
+        path::Path,
+    };
+
+    use base64::Engine;
+    use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+
+    use super::CaptureItem;
+    use crate::{audio_file::AudioFileFormat, error::Error};
+
+    /// Rewrite the comment header of the Ogg file at `path` with the track's
+    /// metadata, staging into a sibling temp file and renaming atomically so a
+    /// failure never leaves a half-tagged file behind.
+    pub(super) fn write_ogg_tags(
+        path: &Path,
+        item: &CaptureItem,
+        _format: AudioFileFormat,
+        cover: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let comment = build_comment_header(item, cover);
+        let staging = path.with_extension("ogg.tagging");
+
+        {
+            let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+            let mut output = File::create(&staging)?;
+            let mut writer = PacketWriter::new(&mut output);
+
+            // The comment header is always the second packet of the logical
+            // Vorbis stream (identification, comment, setup).
+            let mut packet_index = 0usize;
+            while let Some(packet) = reader.read_packet()? {
+                let end = if packet.last_in_stream() {
+                    PacketWriteEndInfo::EndStream
+                } else if packet.last_in_page() {
+                    PacketWriteEndInfo::EndPage
+                } else {
+                    PacketWriteEndInfo::NormalPacket
+                };
+                let data = if packet_index == 1 {
+                    comment.clone()
+                } else {
+                    packet.data.clone()
+                };
+                writer.write_packet(
+                    data.into_boxed_slice(),
+                    packet.stream_serial(),
+                    end,
+                    packet.absgp_page(),
+                )?;
+                packet_index += 1;
+            }
+        }
+
+        fs::rename(&staging, path)?;
+        Ok(())
+    }
+
+    fn build_comment_header(item: &CaptureItem, cover: Option<&[u8]>) -> Vec<u8> {
+        let mut comments = vec![
+            format!("TITLE={}", item.name),
+            format!("ARTIST={}", item.artist),
+            format!("ALBUM={}", item.album),
+            format!("TRACKNUMBER={}", item.track_number),
+        ];
+        if item.disc_number > 0 {
+            comments.push(format!("DISCNUMBER={}", item.disc_number));
+        }
+        if let Some(bytes) = cover {
+            comments.push(format!("METADATA_BLOCK_PICTURE={}", encode_picture(bytes)));
+        }
+
+        let vendor = b"psst";
+        let mut header = Vec::new();
+        header.push(0x03);
+        header.extend_from_slice(b"vorbis");
+        header.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        header.extend_from_slice(vendor);
+        header.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in &comments {
+            header.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            header.extend_from_slice(comment.as_bytes());
+        }
+        header.push(0x01); // framing bit
+        header
+    }
+
+    /// Encode cover art as a FLAC `METADATA_BLOCK_PICTURE` payload, as expected
+    /// inside a Vorbis comment.
+    fn encode_picture(bytes: &[u8]) -> String {
+        let mime = sniff_mime(bytes).as_bytes();
+        let mut block = Vec::new();
+        block.extend_from_slice(&3u32.to_be_bytes()); // front cover
+        block.extend_from_slice(&(mime.len() as u32).to_be_bytes());
+        block.extend_from_slice(mime);
+        block.extend_from_slice(&0u32.to_be_bytes()); // description length
+        block.extend_from_slice(&0u32.to_be_bytes()); // width
+        block.extend_from_slice(&0u32.to_be_bytes()); // height
+        block.extend_from_slice(&0u32.to_be_bytes()); // depth
+        block.extend_from_slice(&0u32.to_be_bytes()); // indexed colors
+        block.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        block.extend_from_slice(bytes);
+        base64::engine::general_purpose::STANDARD.encode(block)
+    }
+
+    /// Sniff the image type from its magic bytes, falling back to JPEG for the
+    /// common case.
+    fn sniff_mime(bytes: &[u8]) -> &'static str {
+        if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+            "image/png"
+        } else if bytes.starts_with(b"GIF8") {
+            "image/gif"
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+            "image/webp"
+        } else {
+            "image/jpeg"
+        }
+    }
+}
+
+/// A small persistent ledger of captured tracks, consulted so re-running a
+/// batch skips already-downloaded items and a crash mid-copy can be resumed.
+mod ledger {
+    use std::{
+        collections::HashMap,
+        ffi::OsString,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{audio_file::AudioFileFormat, item_id::ItemId};
+
+    const LEDGER_FILE: &str = ".psst-capture-ledger.json";
+    const PART_EXTENSION: &str = "part";
+
+    /// Append `.part` to a destination path, e.g. `Song.ogg` -> `Song.ogg.part`.
+    pub(super) fn part_path(destination: &Path) -> PathBuf {
+        let mut name = OsString::from(destination);
+        name.push(".");
+        name.push(PART_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry {
+        item_id: ItemId,
+        format: AudioFileFormat,
+        path: PathBuf,
+        length: u64,
+        complete: bool,
+    }
+
+    #[derive(Default)]
+    pub(super) struct Ledger {
+        path: PathBuf,
+        entries: HashMap<ItemId, Entry>,
+    }
+
+    impl Ledger {
+        /// Load the ledger from `dir`, first sweeping away any stale `.part`
+        /// files so their items are retried.
+        pub(super) fn load(dir: &Path) -> Self {
+            sweep_stale_parts(dir);
+            let path = dir.join(LEDGER_FILE);
+            let entries = fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Vec<Entry>>(&bytes).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| (entry.item_id, entry))
+                .collect();
+            Self { path, entries }
+        }
+
+        /// An item counts as complete only if its file is still present at the
+        /// recorded byte length.
+        pub(super) fn is_complete(&self, item_id: ItemId) -> bool {
+            self.entries.get(&item_id).is_some_and(|entry| {
+                entry.complete
+                    && fs::metadata(&entry.path).is_ok_and(|meta| meta.len() == entry.length)
+            })
+        }
+
+        pub(super) fn mark_complete(
+            &mut self,
+            item_id: ItemId,
+            format: AudioFileFormat,
+            path: PathBuf,
+            length: u64,
+        ) {
+            self.entries.insert(
+                item_id,
+                Entry {
+                    item_id,
+                    format,
+                    path,
+                    length,
+                    complete: true,
+                },
+            );
+            self.persist();
+        }
+
+        fn persist(&self) {
+            let entries: Vec<&Entry> = self.entries.values().collect();
+            match serde_json::to_vec_pretty(&entries) {
+                Ok(bytes) => {
+                    if let Err(err) = fs::write(&self.path, bytes) {
+                        log::warn!("failed to persist capture ledger: {}", err);
+                    }
+                }
+                Err(err) => log::warn!("failed to serialize capture ledger: {}", err),
+            }
+        }
+    }
+
+    fn sweep_stale_parts(dir: &Path) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == PART_EXTENSION) {
+                if let Err(err) = fs::remove_file(&path) {
+                    log::warn!("failed to remove stale part file {:?}: {}", path, err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::item_id::{ItemId, ItemIdType};
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "psst-capturer-test-{}-{}",
+            tag,
+            std::process::id()
+        ));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn quality_presets_expand_in_priority_order() {
+        assert_eq!(
+            QualityPreset::OggOnly.formats(),
+            &[AudioFileFormat::OGG_VORBIS_160]
+        );
+        assert_eq!(
+            QualityPreset::BestBitrate.formats(),
+            &[
+                AudioFileFormat::OGG_VORBIS_320,
+                AudioFileFormat::OGG_VORBIS_160,
+                AudioFileFormat::OGG_VORBIS_96,
+            ]
+        );
+        assert_eq!(QualityPreset::default(), QualityPreset::BestBitrate);
+    }
+
+    #[test]
+    fn part_path_appends_suffix() {
+        assert_eq!(
+            ledger::part_path(Path::new("/music/Artist - Song.ogg")),
+            PathBuf::from("/music/Artist - Song.ogg.part")
+        );
+    }
+
+    #[test]
+    fn ledger_round_trips_recorded_length() {
+        let dir = temp_dir("round-trip");
+        let id = ItemId::new(1, ItemIdType::Track);
+        let path = dir.join("track.ogg");
+        // The length recorded is the file's on-disk size *after* tagging.
+        fs::write(&path, b"tagged-bytes").unwrap();
+
+        let mut ledger = ledger::Ledger::load(&dir);
+        assert!(!ledger.is_complete(id));
+        ledger.mark_complete(id, AudioFileFormat::OGG_VORBIS_320, path.clone(), 12);
+        assert!(ledger.is_complete(id));
+
+        // A fresh load from disk sees the same completion.
+        assert!(ledger::Ledger::load(&dir).is_complete(id));
+
+        // A size mismatch (e.g. a truncated or re-tagged file) invalidates it.
+        fs::write(&path, b"different-length").unwrap();
+        assert!(!ledger::Ledger::load(&dir).is_complete(id));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn load_sweeps_stale_part_files() {
+        let dir = temp_dir("sweep");
+        let part = dir.join("half.ogg.part");
+        let done = dir.join("done.ogg");
+        fs::write(&part, b"partial").unwrap();
+        fs::write(&done, b"ok").unwrap();
+
+        let _ = ledger::Ledger::load(&dir);
+
+        assert!(!part.exists(), "stale .part should be swept");
+        assert!(done.exists(), "finished files must be kept");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn from_io_classifies_connection_errors_as_transient() {
+        let transient = CaptureError::from_io(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!(transient.kind, FailureKind::Transient);
+
+        let fatal = CaptureError::from_io(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(fatal.kind, FailureKind::Fatal);
+    }
 }